@@ -1,225 +1,194 @@
-use std::{error::Error, net::UdpSocket};
-
-// DNS based largely on RFC 1035 which
-// supports only questions and answers
-mod dns {
-    use std::{
-        error::Error,
-        io::{Cursor, Read},
-    };
-
-    mod flags {
-        // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-        // |QR|   Opcode  |AA|TC|RD|RA|   Z    |   RCODE   |
-        // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-        pub const QR: u8 = 0b10000000;
-        pub const OPCODE: u8 = 0b01111000;
-        pub const AA: u8 = 0b00000100;
-        pub const TC: u8 = 0b00000010;
-        pub const RD: u8 = 0b00000001;
-        pub const RA: u8 = 0b10000000;
-        // (the Z bits are reserved) 0b01110000;
-        pub const RCODE: u8 = 0b00001111;
-    }
-
-    #[derive(Debug)]
-    pub enum Opcode {
-        StandardQuery,
-        InverseQuery,
-        ServerStatusRequest,
-    }
-
-    impl TryFrom<u8> for Opcode {
-        type Error = String;
+use std::{
+    error::Error,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::Arc,
+    thread,
+};
+
+mod dns;
+mod zone;
+
+const ADDR: &str = "127.0.0.1:5300";
+const ZONE_FILE: &str = "zone.txt";
+
+// RFC 1035's 512-byte limit applies to plain UDP; a client that sends
+// an EDNS(0) OPT record tells us how much more it can actually accept.
+const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 512;
+const SERVER_UDP_PAYLOAD_SIZE: u16 = 4096;
+
+// The only EDNS(0) version this server implements (RFC 6891 §6.1.3).
+const EDNS_VERSION: u8 = 0;
+
+// Whether the store can serve this record type at all, distinct from
+// whether a zone actually has a matching record: a `Soa` query should
+// answer `NotImplemented`, not `NameError`.
+fn qtype_supported(qtype: dns::QType) -> bool {
+    !matches!(qtype, dns::QType::Soa)
+}
 
-        fn try_from(code: u8) -> Result<Self, Self::Error> {
-            match code {
-                0 => Ok(Self::StandardQuery),
-                1 => Ok(Self::InverseQuery),
-                2 => Ok(Self::ServerStatusRequest),
-                _ => Err(format!("invalid opcode: {code}")),
-            }
-        }
-    }
+// Parses an inbound message and builds the authoritative reply to it
+// from `zone`, shared by both the UDP and TCP serving loops below.
+// Also returns the UDP payload size the client negotiated via EDNS(0)
+// (or the RFC 1035 default), which only the UDP loop needs to honor.
+fn handle_message(buf: &[u8], zone: &zone::Zone) -> Result<(dns::Message, u16), Box<dyn Error>> {
+    let msg = dns::Message::parse(buf)?;
+    println!("{msg:?}");
 
-    #[derive(Debug)]
-    pub enum ResponseCode {
-        NoError,
-        FormatError,
-        ServerFailure,
-        NameError,
-        NotImplemented,
-        Refused,
+    let max_udp_payload_size = msg
+        .edns()
+        .map_or(DEFAULT_UDP_PAYLOAD_SIZE, |edns| edns.udp_payload_size);
+
+    let mut reply = dns::Builder::new(msg.header().id)
+        .response()
+        .opcode(msg.header().opcode)
+        .recursion_desired(msg.header().recursion_desired)
+        .recursion_available(false) // we currently don't support recursion
+        .authoritative_answer(true);
+
+    // Reply with the version this server actually implements, not
+    // whatever the query asked for: echoing the query's version would
+    // claim support for EDNS revisions we don't understand.
+    if msg.edns().is_some() {
+        reply = reply.edns(dns::Edns {
+            udp_payload_size: SERVER_UDP_PAYLOAD_SIZE,
+            extended_rcode: 0,
+            version: EDNS_VERSION,
+            dnssec_ok: false,
+            options: Vec::new(),
+        });
     }
 
-    impl TryFrom<u8> for ResponseCode {
-        type Error = String;
-
-        fn try_from(code: u8) -> Result<Self, Self::Error> {
-            match code {
-                0 => Ok(Self::NoError),
-                1 => Ok(Self::FormatError),
-                2 => Ok(Self::ServerFailure),
-                3 => Ok(Self::NameError),
-                4 => Ok(Self::NotImplemented),
-                5 => Ok(Self::Refused),
-                _ => Err(format!("invalid response code: {code}")),
-            }
+    if msg.header().opcode != dns::Opcode::StandardQuery {
+        reply = reply.response_code(dns::ResponseCode::NotImplemented);
+        for question in msg.questions() {
+            reply = reply.add_question(question.clone());
         }
+        return Ok((reply.build(), max_udp_payload_size));
     }
 
-    #[derive(Debug)]
-    pub struct Header {
-        pub id: u16,
-        pub is_query: bool, // If false, this is a response
-        pub opcode: Opcode,
-        pub is_authoritative_answer: bool,
-        pub truncated: bool,
-        pub recursion_desired: bool,
-        pub recursion_available: bool,
-        pub response_code: ResponseCode,
-        pub questions: u16,
-        pub answers: u16,
-    }
+    let mut rcode = dns::ResponseCode::NoError;
+    for question in msg.questions() {
+        reply = reply.add_question(question.clone());
 
-    impl Header {
-        pub fn parse(buf: &[u8]) -> Result<Header, Box<dyn Error>> {
-            if buf.len() != 12 {
-                return Err(Box::<dyn Error>::from("Slice is not 12 bytes long"));
-            }
-
-            Ok(Self {
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                // |                      ID                       |
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                id: u16::from_be_bytes([buf[0], buf[1]]),
-
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                // |QR|   Opcode  |AA|TC|RD|RA|   Z    |   RCODE   |
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                is_query: (buf[2] & flags::QR) > 0,
-                opcode: Opcode::try_from(u8::from_be((buf[2] & flags::OPCODE) << 1))?,
-                is_authoritative_answer: (buf[2] & flags::AA) > 0,
-                truncated: (buf[2] & flags::TC) > 0,
-                recursion_desired: (buf[2] & flags::RD) > 0,
-                recursion_available: false, // We currently don't support recursion
-                response_code: ResponseCode::try_from(u8::from_be((buf[3] & flags::RCODE) << 4))?,
-
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                // |                    QDCOUNT                    |
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                // |                    ANCOUNT                    |
-                // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                questions: u16::from_be_bytes([buf[4], buf[5]]),
-                answers: u16::from_be_bytes([buf[6], buf[7]]),
-            })
+        if !qtype_supported(question.qtype()) {
+            rcode = dns::ResponseCode::NotImplemented;
+            continue;
         }
-    }
 
-    #[derive(Debug)]
-    pub enum QType {
-        A,
-    }
-
-    impl TryFrom<u16> for QType {
-        type Error = String;
-
-        fn try_from(kind: u16) -> Result<Self, Self::Error> {
-            match kind {
-                1 => Ok(Self::A),
-                _ => Err(format!("invalid qtype: {kind}")),
+        match zone.lookup(question.name(), question.qtype()) {
+            Some(records) => {
+                for rdata in records {
+                    reply = reply.add_answer(dns::Answer::new(
+                        question.name().to_vec(),
+                        dns::QClass::Internet,
+                        300,
+                        rdata.clone_box(),
+                    ));
+                }
             }
+            None => rcode = dns::ResponseCode::NameError,
         }
     }
 
-    #[derive(Debug)]
-    pub enum QClass {
-        Internet,
-    }
+    let reply = reply.response_code(rcode).build();
+    println!("replying with {} answer(s)", reply.answers().len());
+    Ok((reply, max_udp_payload_size))
+}
 
-    impl TryFrom<u16> for QClass {
-        type Error = String;
+// DNS defines the max packet size to be 512 bytes over UDP (RFC 1035),
+// one thread handles each datagram so a slow reply never blocks the
+// socket's next recv_from.
+fn serve_udp(socket: UdpSocket, zone: Arc<zone::Zone>) {
+    loop {
+        let mut buf = [0; 512];
+        let (size, src) = match socket.recv_from(&mut buf) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("udp: failed to receive datagram: {e}");
+                continue;
+            }
+        };
 
-        fn try_from(class: u16) -> Result<Self, Self::Error> {
-            match class {
-                1 => Ok(Self::Internet),
-                _ => Err(format!("invalid qclass: {class}")),
+        let socket = match socket.try_clone() {
+            Ok(socket) => socket,
+            Err(e) => {
+                eprintln!("udp: failed to clone socket: {e}");
+                continue;
             }
-        }
+        };
+        let zone = Arc::clone(&zone);
+        thread::spawn(move || match handle_message(&buf[..size], &zone) {
+            Ok((reply, max_udp_payload_size)) => {
+                let reply = reply.truncate(max_udp_payload_size as usize).to_bytes();
+                if let Err(e) = socket.send_to(&reply, src) {
+                    eprintln!("udp: failed to send reply to {src}: {e}");
+                }
+            }
+            Err(e) => eprintln!("udp: failed to handle message from {src}: {e}"),
+        });
     }
+}
 
-    // FIX I would like a Question::parse() method, but
-    //     the question doesn't have a set length, so I
-    //     can't provide it a pre-read slice using Cursor
-    #[derive(Debug)]
-    pub struct Question {
-        name: Vec<String>,
-        qtype: QType,
-        qclass: QClass,
-    }
+// Over TCP, RFC 1035 4.2.2 frames each message with a two-byte
+// big-endian length prefix instead of relying on a single datagram.
+fn serve_tcp(listener: TcpListener, zone: Arc<zone::Zone>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("tcp: failed to accept connection: {e}");
+                continue;
+            }
+        };
 
-    #[derive(Debug)]
-    pub struct Message {
-        header: Header,
-        questions: Vec<Question>,
+        let zone = Arc::clone(&zone);
+        thread::spawn(move || {
+            if let Err(e) = handle_tcp_connection(stream, &zone) {
+                eprintln!("tcp: connection error: {e}");
+            }
+        });
     }
+}
 
-    impl Message {
-        pub fn parse(buf: &[u8]) -> Result<Message, Box<dyn Error>> {
-            let mut cursor = Cursor::new(&buf[..]);
-
-            // Parse the header
-            let mut header_buf = [0; 12];
-            cursor.read_exact(&mut header_buf)?;
-            let header = Header::parse(&header_buf)?;
-
-            // Parse the questions
-            let mut questions: Vec<Question> = Vec::new();
-            for _ in 0..header.questions {
-                let mut labels: Vec<String> = Vec::new();
-                let mut name_len_buf = [0; 1];
-                cursor.read_exact(&mut name_len_buf)?;
-                while name_len_buf[0] != 0 {
-                    let mut label_buf = vec![0; name_len_buf[0] as usize];
-                    cursor.read_exact(&mut label_buf)?;
-                    labels.push(String::from_utf8(label_buf)?);
-                    cursor.read_exact(&mut name_len_buf)?;
-                }
-
-                let mut qtype_buf = [0; 2];
-                let mut qclass_buf = [0; 2];
-                cursor.read_exact(&mut qtype_buf)?;
-                cursor.read_exact(&mut qclass_buf)?;
-
-                let q = Question {
-                    name: labels,
-                    qtype: QType::try_from(u16::from_be_bytes(qtype_buf))?,
-                    qclass: QClass::try_from(u16::from_be_bytes(qclass_buf))?,
-                };
-                questions.push(q);
-            }
+fn handle_tcp_connection(mut stream: TcpStream, zone: &zone::Zone) -> Result<(), Box<dyn Error>> {
+    loop {
+        let mut len_buf = [0; 2];
+        if let Err(e) = stream.read_exact(&mut len_buf) {
+            // The client closing the connection is the normal way a
+            // TCP session ends, not an error.
+            return match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => Ok(()),
+                _ => Err(e.into()),
+            };
+        }
+        let len = u16::from_be_bytes(len_buf) as usize;
 
-            //
+        let mut msg_buf = vec![0; len];
+        stream.read_exact(&mut msg_buf)?;
 
-            Ok(Self { header, questions })
-        }
+        let (reply, _) = handle_message(&msg_buf, zone)?;
+        let reply = reply.to_bytes();
+        stream.write_all(&(reply.len() as u16).to_be_bytes())?;
+        stream.write_all(&reply)?;
     }
 }
 
 // Run using `cargo run & dig @127.0.0.1 -p 5300 fiwippi.net`
+//                        `dig @127.0.0.1 -p 5300 +tcp fiwippi.net`
 fn main() -> Result<(), Box<dyn Error>> {
-    // Bind to a UDP socket
-    let socket = UdpSocket::bind("127.0.0.1:5300")?;
+    let zone = Arc::new(zone::Zone::load(ZONE_FILE)?);
 
-    // Receive the data sent as part of the UDP packet,
-    // DNS defines the max packet size to be 512 bytes
-    // as part of RFC 1035
-    let mut buf = [0; 512];
-    let (size, _) = socket.recv_from(&mut buf)?;
+    let udp = UdpSocket::bind(ADDR)?;
+    let tcp = TcpListener::bind(ADDR)?;
 
-    // Parse the message
-    let msg = dns::Message::parse(&buf[..size]);
-    println!("{msg:?}");
+    let udp_zone = Arc::clone(&zone);
+    let tcp_zone = Arc::clone(&zone);
+    let udp_handle = thread::spawn(move || serve_udp(udp, udp_zone));
+    let tcp_handle = thread::spawn(move || serve_tcp(tcp, tcp_zone));
+
+    udp_handle.join().expect("udp serving thread panicked");
+    tcp_handle.join().expect("tcp serving thread panicked");
 
     Ok(())
 }
@@ -0,0 +1,213 @@
+// An authoritative zone: records loaded from a simple zone file and
+// kept in memory, keyed by owner name, so `Zone::lookup` can answer
+// questions without touching disk again (modeled loosely on the
+// zone store in the external goatns project).
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use crate::dns::{
+    ARecord, AaaaRecord, CnameRecord, MxRecord, NsRecord, PtrRecord, QType, RData, TxtRecord,
+};
+
+// The owner name as lowercased labels, used as the lookup key so
+// matching is case-insensitive per RFC 1035 §3.1.
+type Key = Vec<String>;
+
+fn normalise(name: &[String]) -> Key {
+    name.iter()
+        .map(|label| label.to_ascii_lowercase())
+        .collect()
+}
+
+fn split_name(name: &str) -> Vec<String> {
+    name.trim_end_matches('.')
+        .split('.')
+        .map(str::to_string)
+        .collect()
+}
+
+// A TXT record's rdata is a sequence of character-strings, each at
+// most 255 bytes (RFC 1035 §3.3): a zone file value longer than that
+// is split across as many character-strings as it takes rather than
+// truncated, since `TxtRecord::to_bytes` would silently wrap a
+// single over-length string's length byte mod 256.
+const MAX_CHARACTER_STRING_LEN: usize = 255;
+
+fn chunk_txt(text: &str) -> Vec<Vec<u8>> {
+    text.as_bytes()
+        .chunks(MAX_CHARACTER_STRING_LEN)
+        .map(<[u8]>::to_vec)
+        .collect()
+}
+
+fn matching(records: &[Box<dyn RData>], qtype: QType) -> Vec<&dyn RData> {
+    records
+        .iter()
+        .filter(|r| r.qtype() == qtype)
+        .map(Box::as_ref)
+        .collect()
+}
+
+// RFC 1034 §3.6.2: an owner that holds a CNAME but no record of the
+// queried type must still return the CNAME rather than an empty
+// answer, so a resolver following it can chase the alias itself.
+fn matching_or_cname(records: &[Box<dyn RData>], qtype: QType) -> Vec<&dyn RData> {
+    let hits = matching(records, qtype);
+    if !hits.is_empty() || qtype == QType::Cname {
+        return hits;
+    }
+    matching(records, QType::Cname)
+}
+
+#[derive(Debug, Default)]
+pub struct Zone {
+    records: HashMap<Key, Vec<Box<dyn RData>>>,
+}
+
+impl Zone {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Parses a simple zone file: one record per non-empty, non-comment
+    // line of `name TYPE rdata...`, e.g. `fiwippi.net A 127.0.0.1`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let mut zone = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            zone.add_line(line)?;
+        }
+        Ok(zone)
+    }
+
+    fn add_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let mut fields = line.split_whitespace();
+        let name = fields.next().ok_or("zone line missing a name")?;
+        let qtype = fields.next().ok_or("zone line missing a record type")?;
+
+        let rdata: Box<dyn RData> = match qtype.to_ascii_uppercase().as_str() {
+            "A" => Box::new(ARecord(
+                fields
+                    .next()
+                    .ok_or("A record missing an address")?
+                    .parse()?,
+            )),
+            "AAAA" => Box::new(AaaaRecord(
+                fields
+                    .next()
+                    .ok_or("AAAA record missing an address")?
+                    .parse()?,
+            )),
+            "CNAME" => Box::new(CnameRecord(split_name(
+                fields.next().ok_or("CNAME record missing a target")?,
+            ))),
+            "NS" => Box::new(NsRecord(split_name(
+                fields.next().ok_or("NS record missing a target")?,
+            ))),
+            "PTR" => Box::new(PtrRecord(split_name(
+                fields.next().ok_or("PTR record missing a target")?,
+            ))),
+            "MX" => {
+                let preference = fields
+                    .next()
+                    .ok_or("MX record missing a preference")?
+                    .parse()?;
+                let exchange = split_name(fields.next().ok_or("MX record missing an exchange")?);
+                Box::new(MxRecord {
+                    preference,
+                    exchange,
+                })
+            }
+            "TXT" => Box::new(TxtRecord(chunk_txt(&fields.collect::<Vec<_>>().join(" ")))),
+            other => return Err(format!("unsupported zone record type: {other}").into()),
+        };
+
+        self.records
+            .entry(normalise(&split_name(name)))
+            .or_default()
+            .push(rdata);
+        Ok(())
+    }
+
+    // Looks up records for `name`/`qtype`. Falls back to a wildcard
+    // owner (`*.<parent>`, RFC 1035 §4.3.3) when there's no exact
+    // match. Returns `None` when `name` isn't present in the zone at
+    // all (the caller should answer `NameError`), or `Some` (possibly
+    // empty, when the name exists but not with this record type).
+    pub fn lookup(&self, name: &[String], qtype: QType) -> Option<Vec<&dyn RData>> {
+        let key = normalise(name);
+
+        if let Some(records) = self.records.get(&key) {
+            return Some(matching_or_cname(records, qtype));
+        }
+
+        if key.len() >= 2 {
+            let mut wildcard = vec!["*".to_string()];
+            wildcard.extend_from_slice(&key[1..]);
+            if let Some(records) = self.records.get(&wildcard) {
+                return Some(matching_or_cname(records, qtype));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone_from_lines(lines: &[&str]) -> Zone {
+        let mut zone = Zone::new();
+        for line in lines {
+            zone.add_line(line).expect("valid zone line");
+        }
+        zone
+    }
+
+    #[test]
+    fn wildcard_owner_answers_for_unmatched_subdomains() {
+        let zone = zone_from_lines(&["*.fiwippi.net A 127.0.0.1"]);
+        let records = zone
+            .lookup(&split_name("anything.fiwippi.net"), QType::A)
+            .expect("wildcard owner should match");
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn lookup_is_case_insensitive() {
+        let zone = zone_from_lines(&["fiwippi.net A 127.0.0.1"]);
+        let records = zone
+            .lookup(&split_name("FIWIPPI.NET"), QType::A)
+            .expect("owner name lookup should ignore case");
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn falls_back_to_cname_when_owner_lacks_queried_type() {
+        let zone = zone_from_lines(&["www.fiwippi.net CNAME fiwippi.net"]);
+        let records = zone
+            .lookup(&split_name("www.fiwippi.net"), QType::A)
+            .expect("CNAME owner should still answer an A query");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].qtype(), QType::Cname);
+    }
+
+    #[test]
+    fn txt_values_over_255_bytes_are_split_into_multiple_character_strings() {
+        let long_value = "a".repeat(300);
+        let zone = zone_from_lines(&[&format!("fiwippi.net TXT {long_value}")]);
+        let records = zone
+            .lookup(&split_name("fiwippi.net"), QType::Txt)
+            .expect("TXT owner should match");
+
+        // 300 bytes must come back as two character-strings (255 + 45
+        // bytes), not one whose length byte silently wraps mod 256.
+        let bytes = records[0].to_bytes();
+        assert_eq!(bytes[0], 255);
+        assert_eq!(bytes[256], 45);
+    }
+}